@@ -0,0 +1,354 @@
+// Copyright (C) 2021 Andreas Doerr
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ancestry proofs: show that an earlier MMR is a strict prefix of a later one
+//!
+//! An [`AncestryProof`] lets a verifier who only knows the bagged root of an
+//! MMR at `prev_size` nodes check that it is indeed an earlier state of an
+//! MMR that has since grown to `size` nodes, without replaying every leaf in
+//! between.
+
+use crate::utils::{bag, family_path, fold_path, peaks};
+use crate::{vec, Error, Hash, Merge, Store, Vec};
+
+/// The reconstruction path for a single previous peak that has been absorbed
+/// into a larger current peak.
+pub struct PeakProof {
+    /// Position of the previous peak this path starts from.
+    pub pos: u64,
+    /// Sibling hashes along `family_path(pos, enclosing peak)`.
+    pub siblings: Vec<Hash>,
+}
+
+/// Proof that an MMR at `prev_size` nodes is a prefix of an MMR at `size`
+/// nodes.
+pub struct AncestryProof {
+    /// Size of the earlier MMR.
+    pub prev_size: u64,
+    /// Size of the later MMR.
+    pub size: u64,
+    /// Hashes of `peaks(prev_size)`, left to right.
+    pub prev_peaks: Vec<Hash>,
+    /// Reconstruction paths for previous peaks absorbed into a larger peak,
+    /// one entry per current peak that absorbs at least one previous peak
+    /// without being one itself.
+    pub merged: Vec<PeakProof>,
+    /// Hashes, left to right, of current peaks that absorb no previous peak
+    /// at all — e.g. a peak created entirely from leaves appended after
+    /// `prev_size`. Supplied directly by the prover, the same way
+    /// [`crate::MerkleProof::peaks`] carries peaks a single-leaf proof
+    /// doesn't otherwise cover.
+    pub new_peaks: Vec<Hash>,
+}
+
+/// Generate an [`AncestryProof`] that the MMR at `prev_size` nodes is a
+/// prefix of the MMR at `size` nodes currently held in `store`.
+pub fn gen_ancestry_proof<T, S, M>(store: &S, prev_size: u64, size: u64) -> Result<AncestryProof, Error>
+where
+    T: Clone,
+    S: Store<T>,
+    M: Merge,
+{
+    let prev_peak_pos = peaks(prev_size);
+    if prev_peak_pos.is_empty() {
+        return Err(Error::UnstableSize(prev_size));
+    }
+
+    let new_peak_pos = peaks(size);
+    if new_peak_pos.is_empty() {
+        return Err(Error::UnstableSize(size));
+    }
+
+    let mut prev_peaks = Vec::with_capacity(prev_peak_pos.len());
+    for &pos in &prev_peak_pos {
+        prev_peaks.push(store.peak_hash_at(pos - 1)?);
+    }
+
+    // Current peaks that already appear verbatim among the previous peaks
+    // need no reconstruction; track them alongside the `enclosing` position
+    // of each merged group below so we can tell, afterwards, which current
+    // peaks are accounted for by previous-peak ancestry at all.
+    let mut explained: Vec<u64> = prev_peak_pos.iter().copied().filter(|p| new_peak_pos.contains(p)).collect();
+
+    // Previous peaks absorbed into the same current peak only need a single
+    // reconstruction path: the one rooted at the rightmost (i.e. last
+    // absorbed) member of the group, whose family path folds in the other
+    // group members as ordinary siblings. Generating a path per member would
+    // just pad the proof with redundant data.
+    let mut enclosing_of = vec![None; prev_peak_pos.len()];
+    for (i, &pos) in prev_peak_pos.iter().enumerate() {
+        if !new_peak_pos.contains(&pos) {
+            enclosing_of[i] = Some(new_peak_pos.iter().copied().find(|&np| np > pos).ok_or(Error::InvalidProof)?);
+        }
+    }
+
+    let mut merged = vec![];
+    let mut done = vec![false; prev_peak_pos.len()];
+
+    for (i, &pos) in prev_peak_pos.iter().enumerate().rev() {
+        let enclosing = match enclosing_of[i] {
+            Some(enclosing) if !done[i] => enclosing,
+            _ => continue,
+        };
+
+        for (j, &e) in enclosing_of.iter().enumerate() {
+            if e == Some(enclosing) {
+                done[j] = true;
+            }
+        }
+
+        let path = family_path(pos, enclosing);
+        let mut siblings = Vec::with_capacity(path.len());
+        for &(_, sibling) in &path {
+            siblings.push(store.hash_at(sibling - 1)?);
+        }
+
+        merged.push(PeakProof { pos, siblings });
+        explained.push(enclosing);
+    }
+
+    merged.reverse();
+
+    // Whatever current peak is neither a previous peak verbatim nor the
+    // enclosing peak of a merged group absorbs no previous ancestry at all
+    // (e.g. it's made entirely of leaves appended after `prev_size`) — its
+    // hash has to be supplied directly, the same way `proof.rs`'s `peaks`
+    // field covers a single-leaf proof's other peaks.
+    let mut new_peaks = Vec::new();
+    for &pos in &new_peak_pos {
+        if explained.contains(&pos) {
+            continue;
+        }
+        new_peaks.push(store.peak_hash_at(pos - 1)?);
+    }
+
+    Ok(AncestryProof {
+        prev_size,
+        size,
+        prev_peaks,
+        merged,
+        new_peaks,
+    })
+}
+
+/// Verify that `proof` attests that the MMR rooted at `prev_root` (with
+/// `proof.prev_size` nodes) is a prefix of the MMR rooted at `root` (with
+/// `proof.size` nodes).
+pub fn verify_ancestry_proof<M: Merge>(proof: &AncestryProof, prev_root: &Hash, root: &Hash) -> Result<bool, Error> {
+    let prev_peak_pos = peaks(proof.prev_size);
+    if prev_peak_pos.is_empty() || prev_peak_pos.len() != proof.prev_peaks.len() {
+        return Err(Error::InvalidProof);
+    }
+
+    let new_peak_pos = peaks(proof.size);
+    if new_peak_pos.is_empty() {
+        return Err(Error::InvalidProof);
+    }
+
+    if bag::<M>(&proof.prev_peaks).ok_or(Error::InvalidProof)? != *prev_root {
+        return Ok(false);
+    }
+
+    let mut covered = vec![false; prev_peak_pos.len()];
+    let mut new_hashes: Vec<(u64, Hash)> = Vec::with_capacity(new_peak_pos.len());
+
+    for &pos in &new_peak_pos {
+        if let Some(i) = prev_peak_pos.iter().position(|&p| p == pos) {
+            covered[i] = true;
+            new_hashes.push((pos, proof.prev_peaks[i]));
+        }
+    }
+
+    // reject duplicate or superfluous merged paths: exactly one per group
+    let mut seen = vec![];
+    for mp in &proof.merged {
+        if seen.contains(&mp.pos) {
+            return Err(Error::InvalidProof);
+        }
+        seen.push(mp.pos);
+
+        let i = prev_peak_pos.iter().position(|&p| p == mp.pos).ok_or(Error::InvalidProof)?;
+        let enclosing = new_peak_pos.iter().copied().find(|&np| np > mp.pos).ok_or(Error::InvalidProof)?;
+
+        let path = family_path(mp.pos, enclosing);
+        if path.len() != mp.siblings.len() {
+            return Err(Error::InvalidProof);
+        }
+
+        let start = proof.prev_peaks[i];
+        let acc = fold_path::<M>(mp.pos, start, &path, &mp.siblings);
+        new_hashes.push((enclosing, acc));
+
+        // every previous peak absorbed into `enclosing` must be the path's
+        // start or appear on it as a sibling carrying its claimed hash
+        for (j, &p) in prev_peak_pos.iter().enumerate() {
+            if !new_peak_pos.contains(&p) && new_peak_pos.iter().copied().find(|&np| np > p) == Some(enclosing) {
+                let bound = p == mp.pos
+                    || path.iter().zip(&mp.siblings).any(|(&(_, s), h)| s == p && *h == proof.prev_peaks[j]);
+                if !bound {
+                    return Err(Error::InvalidProof);
+                }
+                covered[j] = true;
+            }
+        }
+    }
+
+    if covered.iter().any(|&c| !c) {
+        return Err(Error::InvalidProof);
+    }
+
+    // Any current peak not already accounted for by `prev_peaks`/`merged`
+    // absorbs no previous peak and must come from `new_peaks` instead.
+    let mut extra_peaks = proof.new_peaks.iter();
+    for &pos in &new_peak_pos {
+        if new_hashes.iter().any(|&(p, _)| p == pos) {
+            continue;
+        }
+        new_hashes.push((pos, *extra_peaks.next().ok_or(Error::InvalidProof)?));
+    }
+    if extra_peaks.next().is_some() || new_hashes.len() != new_peak_pos.len() {
+        return Err(Error::InvalidProof);
+    }
+
+    new_hashes.sort_by_key(|&(pos, _)| pos);
+    let new_peak_hashes: Vec<Hash> = new_hashes.into_iter().map(|(_, h)| h).collect();
+
+    Ok(bag::<M>(&new_peak_hashes).ok_or(Error::InvalidProof)? == *root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VecStore;
+
+    struct TestMerge;
+
+    impl Merge for TestMerge {
+        fn merge(left: &Hash, right: &Hash) -> Hash {
+            let mut out = [0u8; 32];
+            for i in 0..32 {
+                out[i] = left[i].wrapping_add(right[i].wrapping_mul(3)).wrapping_add(1);
+            }
+            out
+        }
+    }
+
+    fn leaf(v: u8) -> Hash {
+        let mut h = [0u8; 32];
+        h[0] = v;
+        h
+    }
+
+    // Builds the 7 node tree documented in `utils::family_path`:
+    //
+    //        7
+    //      /   \
+    //     3     6
+    //    / \   / \
+    //   1   2 4   5
+    fn seven_node_store() -> (VecStore<u8>, [Hash; 7]) {
+        let h1 = leaf(1);
+        let h2 = leaf(2);
+        let h3 = TestMerge::merge(&h1, &h2);
+        let h4 = leaf(4);
+        let h5 = leaf(5);
+        let h6 = TestMerge::merge(&h4, &h5);
+        let h7 = TestMerge::merge(&h3, &h6);
+
+        let mut store = VecStore::<u8>::new();
+        store.hashes = vec![h1, h2, h3, h4, h5, h6, h7].into_iter().map(Some).collect();
+
+        (store, [h1, h2, h3, h4, h5, h6, h7])
+    }
+
+    #[test]
+    fn single_peak_absorbed() {
+        let (store, h) = seven_node_store();
+
+        let proof = gen_ancestry_proof::<u8, _, TestMerge>(&store, 3, 7).unwrap();
+        assert_eq!(proof.prev_peaks, vec![h[2]]);
+        assert_eq!(proof.merged.len(), 1);
+        assert_eq!(proof.merged[0].pos, 3);
+
+        let prev_root = h[2];
+        let root = h[6];
+        assert!(verify_ancestry_proof::<TestMerge>(&proof, &prev_root, &root).unwrap());
+    }
+
+    #[test]
+    fn merged_peak_group() {
+        let (store, h) = seven_node_store();
+
+        // peaks(4) = [3, 4]: both get absorbed into the single peak at 7
+        let proof = gen_ancestry_proof::<u8, _, TestMerge>(&store, 4, 7).unwrap();
+        assert_eq!(proof.prev_peaks, vec![h[2], h[3]]);
+        // only the rightmost member of the group (peak 4) carries a path
+        assert_eq!(proof.merged.len(), 1);
+        assert_eq!(proof.merged[0].pos, 4);
+
+        let prev_root = TestMerge::merge(&h[2], &h[3]);
+        let root = h[6];
+        assert!(verify_ancestry_proof::<TestMerge>(&proof, &prev_root, &root).unwrap());
+    }
+
+    #[test]
+    fn new_peak_without_previous_ancestry() {
+        let (store, h) = seven_node_store();
+
+        // peaks(3) = [3], peaks(4) = [3, 4]: node 4 is brand new data with no
+        // previous-peak ancestry at all, so it must come through `new_peaks`
+        // rather than `merged`.
+        let proof = gen_ancestry_proof::<u8, _, TestMerge>(&store, 3, 4).unwrap();
+        assert_eq!(proof.prev_peaks, vec![h[2]]);
+        assert!(proof.merged.is_empty());
+        assert_eq!(proof.new_peaks, vec![h[3]]);
+
+        let prev_root = h[2];
+        let root = TestMerge::merge(&h[2], &h[3]);
+        assert!(verify_ancestry_proof::<TestMerge>(&proof, &prev_root, &root).unwrap());
+    }
+
+    #[test]
+    fn tampered_root_rejected() {
+        let (store, h) = seven_node_store();
+
+        let proof = gen_ancestry_proof::<u8, _, TestMerge>(&store, 3, 7).unwrap();
+        let prev_root = h[2];
+        let wrong_root = h[0];
+
+        assert!(!verify_ancestry_proof::<TestMerge>(&proof, &prev_root, &wrong_root).unwrap());
+    }
+
+    #[test]
+    fn padded_proof_rejected() {
+        let (store, h) = seven_node_store();
+
+        let mut proof = gen_ancestry_proof::<u8, _, TestMerge>(&store, 4, 7).unwrap();
+        // duplicate the single legitimate path: a padded, non-minimal proof
+        let dup = PeakProof {
+            pos: proof.merged[0].pos,
+            siblings: proof.merged[0].siblings.clone(),
+        };
+        proof.merged.push(dup);
+
+        let prev_root = TestMerge::merge(&h[2], &h[3]);
+        let root = h[6];
+
+        assert_eq!(
+            verify_ancestry_proof::<TestMerge>(&proof, &prev_root, &root),
+            Err(Error::InvalidProof)
+        );
+    }
+}
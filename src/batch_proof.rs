@@ -0,0 +1,346 @@
+// Copyright (C) 2021 Andreas Doerr
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Batch membership proofs for multiple leaves
+//!
+//! A [`BatchProof`] proves membership of many leaves at once, deduplicating
+//! the overlapping parts of their [`family_path`](crate::utils::family_path)s:
+//! a sibling is only included once, and never if it is itself recomputable
+//! from two leaves (or already-derived nodes) already covered by the proof.
+
+use crate::utils::{bag, family, is_left, peaks};
+use crate::{vec, Error, Hash, Merge, Store, Vec};
+
+/// Batch membership proof for a set of leaves in an MMR of `size` nodes.
+pub struct BatchProof {
+    /// Sorted, deduplicated positions of the leaves this proof covers.
+    pub positions: Vec<u64>,
+    /// Size of the MMR the proof was generated against.
+    pub size: u64,
+    /// The minimal frontier of `(position, hash)` pairs needed, besides the
+    /// claimed leaves, to fold every covered leaf up to its peak.
+    pub siblings: Vec<(u64, Hash)>,
+    /// Hashes of the peaks that none of `positions` falls under, in
+    /// left-to-right order.
+    pub peaks: Vec<Hash>,
+}
+
+/// Generate a [`BatchProof`] for `positions` in the MMR of `size` nodes
+/// currently held in `store`.
+pub fn gen_batch_proof<T, S, M>(store: &S, positions: &[u64], size: u64) -> Result<BatchProof, Error>
+where
+    T: Clone,
+    S: Store<T>,
+    M: Merge,
+{
+    let peak_pos = peaks(size);
+    if peak_pos.is_empty() {
+        return Err(Error::UnstableSize(size));
+    }
+
+    let mut positions = positions.to_vec();
+    positions.sort_unstable();
+    positions.dedup();
+
+    let mut known = positions.clone();
+    let mut siblings: Vec<(u64, Hash)> = vec![];
+
+    expand(&mut known, &peak_pos);
+
+    loop {
+        let next = known
+            .iter()
+            .copied()
+            .find(|pos| !peak_pos.contains(pos) && !known.contains(&family(*pos).0));
+
+        let pos = match next {
+            Some(pos) => pos,
+            None => break,
+        };
+
+        let (parent, sibling) = family(pos);
+        if !known.contains(&sibling) {
+            siblings.push((sibling, store.hash_at(sibling - 1)?));
+            known.push(sibling);
+        }
+        known.push(parent);
+
+        expand(&mut known, &peak_pos);
+    }
+
+    let mut peak_hashes = Vec::new();
+    for &p in &peak_pos {
+        if !known.contains(&p) {
+            peak_hashes.push(store.peak_hash_at(p - 1)?);
+        }
+    }
+
+    Ok(BatchProof {
+        positions,
+        size,
+        siblings,
+        peaks: peak_hashes,
+    })
+}
+
+/// Grow `known` by folding any pair of positions whose sibling is already
+/// known into their (now known) parent, until no more progress can be made.
+fn expand(known: &mut Vec<u64>, peak_pos: &[u64]) {
+    loop {
+        let mut progressed = false;
+        let mut i = 0;
+
+        while i < known.len() {
+            let pos = known[i];
+            i += 1;
+
+            if peak_pos.contains(&pos) {
+                continue;
+            }
+
+            let (parent, sibling) = family(pos);
+            if known.contains(&parent) {
+                continue;
+            }
+
+            if known.contains(&sibling) {
+                known.push(parent);
+                progressed = true;
+            }
+        }
+
+        if !progressed {
+            break;
+        }
+    }
+}
+
+/// Verify that `proof` attests that `leaves` (aligned with `proof.positions`)
+/// are members of the MMR rooted at `root`.
+///
+/// Rejects a non-minimal proof: every entry in `proof.siblings` must be
+/// used by some fold step and must not already be derivable by folding
+/// `leaves` alone.
+pub fn verify_batch_proof<M: Merge>(proof: &BatchProof, leaves: &[(u64, Hash)], root: &Hash) -> Result<bool, Error> {
+    let peak_pos = peaks(proof.size);
+    if peak_pos.is_empty() || leaves.len() != proof.positions.len() {
+        return Err(Error::InvalidProof);
+    }
+
+    for (&pos, &(leaf_pos, _)) in proof.positions.iter().zip(leaves.iter()) {
+        if pos != leaf_pos {
+            return Err(Error::InvalidProof);
+        }
+    }
+
+    let mut known: Vec<(u64, Hash)> = leaves.to_vec();
+    known.extend(proof.siblings.iter().copied());
+
+    let mut seen = vec![];
+    for &(pos, _) in &known {
+        if seen.contains(&pos) {
+            return Err(Error::InvalidProof);
+        }
+        seen.push(pos);
+    }
+
+    // A sibling is padding, not evidence, if its position is already
+    // reachable by folding the claimed leaves alone: the prover didn't need
+    // it, they just attached it. Reject before even checking whether it
+    // happens to be "used" by some fold step below.
+    let mut leaf_closure = proof.positions.clone();
+    expand(&mut leaf_closure, &peak_pos);
+    if proof.siblings.iter().any(|&(pos, _)| leaf_closure.contains(&pos)) {
+        return Err(Error::InvalidProof);
+    }
+
+    let mut used = vec![false; proof.siblings.len()];
+
+    loop {
+        let mut progressed = false;
+        let mut i = 0;
+
+        while i < known.len() {
+            let (pos, hash) = known[i];
+            i += 1;
+
+            if peak_pos.contains(&pos) {
+                continue;
+            }
+
+            let (parent, sibling) = family(pos);
+            if known.iter().any(|&(p, _)| p == parent) {
+                continue;
+            }
+
+            if let Some(idx) = known.iter().position(|&(p, _)| p == sibling) {
+                let (_, sib_hash) = known[idx];
+                let parent_hash = if is_left(pos) {
+                    M::merge(&hash, &sib_hash)
+                } else {
+                    M::merge(&sib_hash, &hash)
+                };
+
+                known.push((parent, parent_hash));
+                progressed = true;
+
+                for folded in [pos, sibling] {
+                    if let Some(j) = proof.siblings.iter().position(|&(p, _)| p == folded) {
+                        used[j] = true;
+                    }
+                }
+            }
+        }
+
+        if !progressed {
+            break;
+        }
+    }
+
+    if used.iter().any(|&u| !u) {
+        return Err(Error::InvalidProof);
+    }
+
+    let mut all_peaks = Vec::with_capacity(peak_pos.len());
+    let mut extra_peaks = proof.peaks.iter();
+    for &p in &peak_pos {
+        if let Some(&(_, hash)) = known.iter().find(|&&(pos, _)| pos == p) {
+            all_peaks.push(hash);
+        } else {
+            all_peaks.push(*extra_peaks.next().ok_or(Error::InvalidProof)?);
+        }
+    }
+    if extra_peaks.next().is_some() {
+        return Err(Error::InvalidProof);
+    }
+
+    Ok(bag::<M>(&all_peaks).ok_or(Error::InvalidProof)? == *root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VecStore;
+
+    struct TestMerge;
+
+    impl Merge for TestMerge {
+        fn merge(left: &Hash, right: &Hash) -> Hash {
+            let mut out = [0u8; 32];
+            for i in 0..32 {
+                out[i] = left[i].wrapping_add(right[i].wrapping_mul(3)).wrapping_add(1);
+            }
+            out
+        }
+    }
+
+    fn leaf(v: u8) -> Hash {
+        let mut h = [0u8; 32];
+        h[0] = v;
+        h
+    }
+
+    // Builds the 7 node tree documented in `utils::family_path`.
+    fn seven_node_store() -> (VecStore<u8>, [Hash; 7]) {
+        let h1 = leaf(1);
+        let h2 = leaf(2);
+        let h3 = TestMerge::merge(&h1, &h2);
+        let h4 = leaf(4);
+        let h5 = leaf(5);
+        let h6 = TestMerge::merge(&h4, &h5);
+        let h7 = TestMerge::merge(&h3, &h6);
+
+        let mut store = VecStore::<u8>::new();
+        store.hashes = vec![h1, h2, h3, h4, h5, h6, h7].into_iter().map(Some).collect();
+
+        (store, [h1, h2, h3, h4, h5, h6, h7])
+    }
+
+    #[test]
+    fn clustered_leaves_share_path() {
+        let (store, h) = seven_node_store();
+
+        // leaves 1 and 2 share the same parent (3): no sibling hash needed
+        // between them, only the path from 3 up to the peak.
+        let proof = gen_batch_proof::<u8, _, TestMerge>(&store, &[1, 2], 7).unwrap();
+        assert_eq!(proof.positions, vec![1, 2]);
+        assert_eq!(proof.siblings, vec![(6, h[5])]);
+        assert!(proof.peaks.is_empty());
+
+        let root = crate::root::<u8, _, TestMerge>(7, &store).unwrap();
+        let leaves = vec![(1, h[0]), (2, h[1])];
+        assert!(verify_batch_proof::<TestMerge>(&proof, &leaves, &root).unwrap());
+    }
+
+    #[test]
+    fn single_leaf_batch_matches_family_path() {
+        let (store, h) = seven_node_store();
+
+        let proof = gen_batch_proof::<u8, _, TestMerge>(&store, &[4], 7).unwrap();
+        assert_eq!(proof.siblings, vec![(5, h[4]), (3, h[2])]);
+
+        let root = crate::root::<u8, _, TestMerge>(7, &store).unwrap();
+        let leaves = vec![(4, h[3])];
+        assert!(verify_batch_proof::<TestMerge>(&proof, &leaves, &root).unwrap());
+    }
+
+    #[test]
+    fn tampered_leaf_rejected() {
+        let (store, h) = seven_node_store();
+
+        let proof = gen_batch_proof::<u8, _, TestMerge>(&store, &[1, 2], 7).unwrap();
+        let root = crate::root::<u8, _, TestMerge>(7, &store).unwrap();
+
+        let leaves = vec![(1, h[0]), (2, h[0])];
+        assert!(!verify_batch_proof::<TestMerge>(&proof, &leaves, &root).unwrap());
+    }
+
+    #[test]
+    fn padded_proof_rejected() {
+        let (store, h) = seven_node_store();
+
+        let mut proof = gen_batch_proof::<u8, _, TestMerge>(&store, &[1, 2], 7).unwrap();
+        // node 4 is not on any path from the claimed leaves to their peak;
+        // attaching its hash as a "sibling" pads the proof without being used
+        proof.siblings.push((4, h[3]));
+
+        let root = crate::root::<u8, _, TestMerge>(7, &store).unwrap();
+        let leaves = vec![(1, h[0]), (2, h[1])];
+
+        assert_eq!(
+            verify_batch_proof::<TestMerge>(&proof, &leaves, &root),
+            Err(Error::InvalidProof)
+        );
+    }
+
+    #[test]
+    fn padded_proof_with_derivable_sibling_rejected() {
+        let (store, h) = seven_node_store();
+
+        let mut proof = gen_batch_proof::<u8, _, TestMerge>(&store, &[1, 2], 7).unwrap();
+        // node 3's hash is already derivable by folding the claimed leaves
+        // (1 and 2) together; attaching it as a "sibling" is padding even
+        // though it does get folded in on the way to peak 7
+        proof.siblings.push((3, h[2]));
+
+        let root = crate::root::<u8, _, TestMerge>(7, &store).unwrap();
+        let leaves = vec![(1, h[0]), (2, h[1])];
+
+        assert_eq!(
+            verify_batch_proof::<TestMerge>(&proof, &leaves, &root),
+            Err(Error::InvalidProof)
+        );
+    }
+}
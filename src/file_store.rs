@@ -0,0 +1,315 @@
+// Copyright (C) 2021 Andreas Doerr
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! File-backed `Store` implementation
+//!
+//! `hashes.dat` is an append-only file of fixed 32-byte records indexed
+//! directly by position, so `hash_at(idx)` is a single `seek`/`read`.
+//! `data.dat` holds the length-prefixed byte encoding of the stored
+//! elements; [`FileStore::data_at`] reads one back by the 0-based order in
+//! which it was [`Store::append`]ed, same as [`crate::VecStore::data`].
+//! Both files are opened for read/write and truncated on [`Store::rewind`]
+//! back to the byte offsets recorded at the last checkpoint.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::{Error, Hash, Store};
+
+const HASH_SIZE: u64 = 32;
+
+/// Rebuild the offsets of each length-prefixed record in `data_file` by
+/// walking it once from the start; only needed on [`FileStore::open`], since
+/// [`FileStore::flush`] tracks new offsets as it writes them.
+fn scan_data_offsets(data_file: &File, data_len: u64) -> Result<Vec<u64>, Error> {
+    let mut file = data_file.try_clone().map_err(|e| Error::Io(e.to_string()))?;
+    file.seek(SeekFrom::Start(0)).map_err(|e| Error::Io(e.to_string()))?;
+
+    let mut offsets = vec![];
+    let mut pos = 0;
+    while pos < data_len {
+        offsets.push(pos);
+
+        let mut len_buf = [0u8; 4];
+        file.read_exact(&mut len_buf).map_err(|e| Error::Io(e.to_string()))?;
+        let len = u32::from_le_bytes(len_buf) as u64;
+
+        file.seek(SeekFrom::Current(len as i64)).map_err(|e| Error::Io(e.to_string()))?;
+        pos += 4 + len;
+    }
+
+    Ok(offsets)
+}
+
+/// A disk-backed [`Store`] so an MMR survives process restarts.
+pub struct FileStore<T> {
+    hash_file: File,
+    data_file: File,
+    /// Number of hash records flushed to `hash_file`.
+    hashes_len: u64,
+    /// Byte length of `data_file`.
+    data_len: u64,
+    /// Hashes appended since the last flush.
+    tail: Vec<Hash>,
+    /// Elements appended since the last flush.
+    tail_data: Vec<T>,
+    /// Byte offset, within `data_file`, of each flushed element's
+    /// length-prefix record, in append order; `data_at(idx)` indexes this.
+    data_offsets: Vec<u64>,
+    /// `(checkpoint_id, hashes_len, data_len, data_offsets.len())` records,
+    /// most recent last.
+    checkpoints: Vec<(u64, u64, u64, usize)>,
+}
+
+impl<T> FileStore<T>
+where
+    T: Clone + AsRef<[u8]>,
+{
+    /// Open (creating if necessary) a file-backed store rooted at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        std::fs::create_dir_all(path.as_ref()).map_err(|e| Error::Io(e.to_string()))?;
+
+        let hash_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.as_ref().join("hashes.dat"))
+            .map_err(|e| Error::Io(e.to_string()))?;
+
+        let data_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path.as_ref().join("data.dat"))
+            .map_err(|e| Error::Io(e.to_string()))?;
+
+        let hashes_len = hash_file.metadata().map_err(|e| Error::Io(e.to_string()))?.len() / HASH_SIZE;
+        let data_len = data_file.metadata().map_err(|e| Error::Io(e.to_string()))?.len();
+
+        let data_offsets = scan_data_offsets(&data_file, data_len)?;
+
+        Ok(FileStore {
+            hash_file,
+            data_file,
+            hashes_len,
+            data_len,
+            tail: vec![],
+            tail_data: vec![],
+            data_offsets,
+            checkpoints: vec![],
+        })
+    }
+
+    /// Write the in-memory tail buffer out to the backing files.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.hash_file
+            .seek(SeekFrom::Start(self.hashes_len * HASH_SIZE))
+            .map_err(|e| Error::Io(e.to_string()))?;
+        for hash in self.tail.drain(..) {
+            self.hash_file.write_all(&hash).map_err(|e| Error::Io(e.to_string()))?;
+            self.hashes_len += 1;
+        }
+
+        self.data_file
+            .seek(SeekFrom::Start(self.data_len))
+            .map_err(|e| Error::Io(e.to_string()))?;
+        for elem in self.tail_data.drain(..) {
+            let bytes = elem.as_ref();
+            self.data_offsets.push(self.data_len);
+            self.data_file
+                .write_all(&(bytes.len() as u32).to_le_bytes())
+                .map_err(|e| Error::Io(e.to_string()))?;
+            self.data_file.write_all(bytes).map_err(|e| Error::Io(e.to_string()))?;
+            self.data_len += 4 + bytes.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Flush the tail buffer and fsync both backing files.
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.flush()?;
+        self.hash_file.sync_all().map_err(|e| Error::Io(e.to_string()))?;
+        self.data_file.sync_all().map_err(|e| Error::Io(e.to_string()))
+    }
+
+    /// Read back the element appended at leaf position `idx` (0-based,
+    /// matching the order of [`Store::append`] calls and [`VecStore::data`]'s
+    /// indexing), decoding the bytes `flush` wrote for it.
+    ///
+    /// [`VecStore::data`]: crate::VecStore::data
+    pub fn data_at(&self, idx: u64) -> Result<T, Error>
+    where
+        T: for<'a> TryFrom<&'a [u8]>,
+        for<'a> <T as TryFrom<&'a [u8]>>::Error: std::fmt::Debug,
+    {
+        let &offset = self.data_offsets.get(idx as usize).ok_or(Error::MissingDataAtIndex(idx))?;
+
+        let mut file = self.data_file.try_clone().map_err(|e| Error::Io(e.to_string()))?;
+        file.seek(SeekFrom::Start(offset)).map_err(|e| Error::Io(e.to_string()))?;
+
+        let mut len_buf = [0u8; 4];
+        file.read_exact(&mut len_buf).map_err(|e| Error::Io(e.to_string()))?;
+        let len = u32::from_le_bytes(len_buf) as usize;
+
+        let mut bytes = vec![0u8; len];
+        file.read_exact(&mut bytes).map_err(|e| Error::Io(e.to_string()))?;
+
+        T::try_from(&bytes).map_err(|e| Error::Decode(format!("{e:?}")))
+    }
+}
+
+impl<T> Store<T> for FileStore<T>
+where
+    T: Clone + AsRef<[u8]>,
+{
+    fn append(&mut self, elem: &T, hashes: &[Hash]) -> Result<(), Error> {
+        self.tail.extend_from_slice(hashes);
+        self.tail_data.push(elem.clone());
+        self.flush()
+    }
+
+    fn hash_at(&self, idx: u64) -> Result<Hash, Error> {
+        if idx < self.hashes_len {
+            let mut file = self.hash_file.try_clone().map_err(|e| Error::Io(e.to_string()))?;
+            file.seek(SeekFrom::Start(idx * HASH_SIZE))
+                .map_err(|e| Error::Io(e.to_string()))?;
+
+            let mut hash = [0u8; 32];
+            file.read_exact(&mut hash).map_err(|e| Error::Io(e.to_string()))?;
+            return Ok(hash);
+        }
+
+        self.tail
+            .get((idx - self.hashes_len) as usize)
+            .copied()
+            .ok_or(Error::MissingHashAtIndex(idx))
+    }
+
+    fn peak_hash_at(&self, idx: u64) -> Result<Hash, Error> {
+        self.hash_at(idx)
+    }
+
+    fn checkpoint(&mut self, id: u64) {
+        self.checkpoints.push((
+            id,
+            self.hashes_len + self.tail.len() as u64,
+            self.data_len,
+            self.data_offsets.len(),
+        ));
+    }
+
+    fn rewind(&mut self) -> Result<(), Error> {
+        let (_, hashes_len, data_len, data_offsets_len) = self.checkpoints.pop().ok_or(Error::NoCheckpoint)?;
+
+        self.tail.clear();
+        self.tail_data.clear();
+
+        self.hash_file
+            .set_len(hashes_len * HASH_SIZE)
+            .map_err(|e| Error::Io(e.to_string()))?;
+        self.data_file.set_len(data_len).map_err(|e| Error::Io(e.to_string()))?;
+
+        self.hashes_len = hashes_len;
+        self.data_len = data_len;
+        self.data_offsets.truncate(data_offsets_len);
+
+        Ok(())
+    }
+
+    fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(v: u8) -> Hash {
+        let mut h = [0u8; 32];
+        h[0] = v;
+        h
+    }
+
+    fn tmp_dir(name: &str) -> std::path::PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("arber-file-store-test-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn append_and_read_back_survives_reopen() {
+        let dir = tmp_dir("reopen");
+
+        {
+            let mut store = FileStore::<Vec<u8>>::open(&dir).unwrap();
+            store.append(&vec![1u8, 2, 3], &[hash(1), hash(2)]).unwrap();
+            store.sync().unwrap();
+        }
+
+        let store = FileStore::<Vec<u8>>::open(&dir).unwrap();
+        assert_eq!(store.hash_at(0).unwrap(), hash(1));
+        assert_eq!(store.hash_at(1).unwrap(), hash(2));
+        assert!(store.hash_at(2).is_err());
+        assert_eq!(store.data_at(0).unwrap(), vec![1u8, 2, 3]);
+        assert!(store.data_at(1).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rewind_truncates_backing_files() {
+        let dir = tmp_dir("rewind");
+        let mut store = FileStore::<Vec<u8>>::open(&dir).unwrap();
+
+        store.append(&vec![1u8], &[hash(1)]).unwrap();
+        store.checkpoint(0);
+
+        store.append(&vec![2u8], &[hash(2)]).unwrap();
+        store.append(&vec![3u8], &[hash(3)]).unwrap();
+
+        store.rewind().unwrap();
+
+        assert_eq!(store.hash_at(0).unwrap(), hash(1));
+        assert!(store.hash_at(1).is_err());
+        assert_eq!(store.data_at(0).unwrap(), vec![1u8]);
+        assert!(store.data_at(1).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn data_at_survives_reopen() {
+        let dir = tmp_dir("data-reopen");
+
+        {
+            let mut store = FileStore::<Vec<u8>>::open(&dir).unwrap();
+            store.append(&vec![1u8, 2, 3], &[hash(1)]).unwrap();
+            store.append(&vec![4u8, 5], &[hash(2)]).unwrap();
+            store.sync().unwrap();
+        }
+
+        let store = FileStore::<Vec<u8>>::open(&dir).unwrap();
+        assert_eq!(store.data_at(0).unwrap(), vec![1u8, 2, 3]);
+        assert_eq!(store.data_at(1).unwrap(), vec![4u8, 5]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
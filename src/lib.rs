@@ -0,0 +1,75 @@
+// Copyright (C) 2021 Andreas Doerr
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `arber` is a 64-bit addressable Merkle Mountain Range (MMR)
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(not(feature = "std"))]
+pub(crate) use alloc::{string::String, vec, vec::Vec};
+#[cfg(feature = "std")]
+pub(crate) use std::{string::String, vec, vec::Vec};
+
+mod ancestry;
+mod batch_proof;
+#[cfg(feature = "std")]
+mod file_store;
+mod proof;
+mod retention;
+mod store;
+mod utils;
+
+pub use ancestry::{gen_ancestry_proof, verify_ancestry_proof, AncestryProof, PeakProof};
+pub use batch_proof::{gen_batch_proof, verify_batch_proof, BatchProof};
+#[cfg(feature = "std")]
+pub use file_store::FileStore;
+pub use proof::{gen_proof, root, verify_proof, MerkleProof};
+pub use retention::{PruningStore, Retention};
+pub use store::{Store, VecStore};
+
+/// A single MMR node hash.
+pub type Hash = [u8; 32];
+
+/// Combines the hashes of two child nodes into the hash of their parent.
+///
+/// `arber` stays agnostic of the concrete hashing algorithm; callers plug in
+/// their own `Merge` implementation (e.g. backed by `blake2` or `sha2`).
+pub trait Merge {
+    /// Merge `left` and `right` into the hash of their parent node.
+    fn merge(left: &Hash, right: &Hash) -> Hash;
+}
+
+/// Error conditions produced by MMR operations.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// No hash is stored at the given index.
+    MissingHashAtIndex(u64),
+    /// The given number of nodes does not describe a stable MMR.
+    UnstableSize(u64),
+    /// A proof failed to verify, either structurally or cryptographically.
+    InvalidProof,
+    /// `rewind` was called on a store with no checkpoint to rewind to.
+    NoCheckpoint,
+    /// The hash at the given index has been pruned and is no longer stored.
+    Pruned(u64),
+    /// No element is stored at the given index.
+    MissingDataAtIndex(u64),
+    /// A stored element's bytes could not be decoded back into `T`.
+    Decode(String),
+    /// A backing-store I/O operation failed.
+    Io(String),
+}
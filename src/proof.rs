@@ -0,0 +1,218 @@
+// Copyright (C) 2021 Andreas Doerr
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Membership proofs for a single leaf
+//!
+//! A [`MerkleProof`] lets a caller independently check that a leaf is part
+//! of an MMR with a given (bagged) root, without access to a [`Store`].
+
+use crate::utils::{bag, family_path, fold_path, peaks};
+use crate::{Error, Hash, Merge, Store, Vec};
+
+/// Membership proof for the leaf at position `pos` in an MMR of `size`
+/// nodes.
+pub struct MerkleProof {
+    /// Position of the leaf this proof is for.
+    pub pos: u64,
+    /// Size of the MMR the proof was generated against.
+    pub size: u64,
+    /// Sibling hashes along `family_path(pos, enclosing peak)`.
+    pub siblings: Vec<Hash>,
+    /// Hashes of the current peaks other than the one enclosing `pos`, in
+    /// left-to-right order.
+    pub peaks: Vec<Hash>,
+}
+
+/// Generate a [`MerkleProof`] for the leaf at `pos` in the MMR of `size`
+/// nodes currently held in `store`.
+pub fn gen_proof<T, S, M>(store: &S, pos: u64, size: u64) -> Result<MerkleProof, Error>
+where
+    T: Clone,
+    S: Store<T>,
+    M: Merge,
+{
+    let peak_pos = peaks(size);
+    if peak_pos.is_empty() {
+        return Err(Error::UnstableSize(size));
+    }
+
+    let enclosing = peak_pos.iter().copied().find(|&p| p >= pos).ok_or(Error::InvalidProof)?;
+    let path = family_path(pos, enclosing);
+
+    let mut siblings = Vec::with_capacity(path.len());
+    for &(_, sibling) in &path {
+        siblings.push(store.hash_at(sibling - 1)?);
+    }
+
+    let mut peak_hashes = Vec::with_capacity(peak_pos.len() - 1);
+    for &p in &peak_pos {
+        if p != enclosing {
+            peak_hashes.push(store.peak_hash_at(p - 1)?);
+        }
+    }
+
+    Ok(MerkleProof {
+        pos,
+        size,
+        siblings,
+        peaks: peak_hashes,
+    })
+}
+
+/// Verify that `proof` attests that `leaf_hash` is a member of the MMR
+/// rooted at `root`.
+pub fn verify_proof<M: Merge>(proof: &MerkleProof, leaf_hash: &Hash, root: &Hash) -> Result<bool, Error> {
+    let peak_pos = peaks(proof.size);
+    if peak_pos.is_empty() || proof.peaks.len() + 1 != peak_pos.len() {
+        return Err(Error::InvalidProof);
+    }
+
+    let enclosing = peak_pos.iter().copied().find(|&p| p >= proof.pos).ok_or(Error::InvalidProof)?;
+    let path = family_path(proof.pos, enclosing);
+    if path.len() != proof.siblings.len() {
+        return Err(Error::InvalidProof);
+    }
+
+    let peak_hash = fold_path::<M>(proof.pos, *leaf_hash, &path, &proof.siblings);
+
+    let mut all_peaks = Vec::with_capacity(peak_pos.len());
+    let mut other_peaks = proof.peaks.iter();
+    for &p in &peak_pos {
+        if p == enclosing {
+            all_peaks.push(peak_hash);
+        } else {
+            all_peaks.push(*other_peaks.next().ok_or(Error::InvalidProof)?);
+        }
+    }
+    if other_peaks.next().is_some() {
+        return Err(Error::InvalidProof);
+    }
+
+    Ok(bag::<M>(&all_peaks).ok_or(Error::InvalidProof)? == *root)
+}
+
+/// Compute the canonical bagged root of the MMR at `size` nodes currently
+/// held in `store`, the same way [`verify_proof`] reconstructs it.
+pub fn root<T, S, M>(size: u64, store: &S) -> Result<Hash, Error>
+where
+    T: Clone,
+    S: Store<T>,
+    M: Merge,
+{
+    let peak_pos = peaks(size);
+    if peak_pos.is_empty() {
+        return Err(Error::UnstableSize(size));
+    }
+
+    let mut hashes = Vec::with_capacity(peak_pos.len());
+    for &p in &peak_pos {
+        hashes.push(store.peak_hash_at(p - 1)?);
+    }
+
+    bag::<M>(&hashes).ok_or(Error::InvalidProof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VecStore;
+
+    struct TestMerge;
+
+    impl Merge for TestMerge {
+        fn merge(left: &Hash, right: &Hash) -> Hash {
+            let mut out = [0u8; 32];
+            for i in 0..32 {
+                out[i] = left[i].wrapping_add(right[i].wrapping_mul(3)).wrapping_add(1);
+            }
+            out
+        }
+    }
+
+    fn leaf(v: u8) -> Hash {
+        let mut h = [0u8; 32];
+        h[0] = v;
+        h
+    }
+
+    // Builds the 7 node tree documented in `utils::family_path`.
+    fn seven_node_store() -> (VecStore<u8>, [Hash; 7]) {
+        let h1 = leaf(1);
+        let h2 = leaf(2);
+        let h3 = TestMerge::merge(&h1, &h2);
+        let h4 = leaf(4);
+        let h5 = leaf(5);
+        let h6 = TestMerge::merge(&h4, &h5);
+        let h7 = TestMerge::merge(&h3, &h6);
+
+        let mut store = VecStore::<u8>::new();
+        store.hashes = vec![h1, h2, h3, h4, h5, h6, h7].into_iter().map(Some).collect();
+
+        (store, [h1, h2, h3, h4, h5, h6, h7])
+    }
+
+    #[test]
+    fn root_matches_manual_bagging() {
+        let (store, h) = seven_node_store();
+        assert_eq!(root::<u8, _, TestMerge>(7, &store).unwrap(), h[6]);
+    }
+
+    #[test]
+    fn proof_round_trips() {
+        let (store, h) = seven_node_store();
+
+        let proof = gen_proof::<u8, _, TestMerge>(&store, 1, 7).unwrap();
+        assert_eq!(proof.siblings.len(), 2);
+        assert!(proof.peaks.is_empty());
+
+        let root = root::<u8, _, TestMerge>(7, &store).unwrap();
+        assert!(verify_proof::<TestMerge>(&proof, &h[0], &root).unwrap());
+    }
+
+    #[test]
+    fn proof_rejects_wrong_leaf() {
+        let (store, h) = seven_node_store();
+
+        let proof = gen_proof::<u8, _, TestMerge>(&store, 1, 7).unwrap();
+        let root = root::<u8, _, TestMerge>(7, &store).unwrap();
+
+        assert!(!verify_proof::<TestMerge>(&proof, &h[1], &root).unwrap());
+    }
+
+    #[test]
+    fn proof_with_other_peaks() {
+        // peaks(11) = [7, 10, 11]
+        let h1 = leaf(1);
+        let h2 = leaf(2);
+        let h3 = TestMerge::merge(&h1, &h2);
+        let h4 = leaf(4);
+        let h5 = leaf(5);
+        let h6 = TestMerge::merge(&h4, &h5);
+        let h7 = TestMerge::merge(&h3, &h6);
+        let h8 = leaf(8);
+        let h9 = leaf(9);
+        let h10 = TestMerge::merge(&h8, &h9);
+        let h11 = leaf(11);
+
+        let mut store = VecStore::<u8>::new();
+        store.hashes = vec![h1, h2, h3, h4, h5, h6, h7, h8, h9, h10, h11].into_iter().map(Some).collect();
+
+        let proof = gen_proof::<u8, _, TestMerge>(&store, 8, 11).unwrap();
+        assert_eq!(proof.peaks, vec![h7, h11]);
+
+        let root = root::<u8, _, TestMerge>(11, &store).unwrap();
+        assert!(verify_proof::<TestMerge>(&proof, &h8, &root).unwrap());
+    }
+}
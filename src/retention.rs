@@ -0,0 +1,322 @@
+// Copyright (C) 2021 Andreas Doerr
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Leaf-pruning `Store` wrapper
+//!
+//! Long-lived MMRs don't need to keep every hash around: only a witnessed
+//! (`Marked`) leaf and the sibling hashes along its family path to its peak,
+//! plus the current peaks themselves, are ever needed to re-generate a
+//! membership proof.
+//! Everything else (`Ephemeral`) can be dropped.
+//!
+//! [`PruningStore::prune`] and [`PruningStore::compact`] are deliberately
+//! separate steps. `prune` only recomputes, from scratch, which indices are
+//! currently unneeded — reversible, since it never touches the backing
+//! [`Store`], so re-[`mark`](PruningStore::mark)ing a leaf before the next
+//! `prune` call always restores access to its path. `compact` is the one
+//! that actually calls [`Store::free`] on every presently-unneeded index,
+//! which is what shrinks memory; it is a one-way operation, since the
+//! backing store is free to have really discarded those hashes afterwards.
+
+use core::marker::PhantomData;
+
+use crate::utils::{family_path, peaks};
+use crate::{vec, Error, Hash, Store, Vec};
+
+/// Whether a leaf's hashes should be retained once they are no longer needed
+/// to recompute the current peaks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    /// The leaf's hashes may be pruned once they are no longer on the path
+    /// to a current peak.
+    Ephemeral,
+    /// The leaf's hashes are retained so a membership proof can always be
+    /// regenerated for it.
+    Marked,
+}
+
+/// A [`Store`] wrapper that prunes hashes not needed to reconstruct the
+/// current peaks or a membership proof for a [`Retention::Marked`] leaf.
+pub struct PruningStore<T, S> {
+    inner: S,
+    /// Retention tag per node index, same indexing as [`Store::hash_at`].
+    retention: Vec<Retention>,
+    /// Whether the hash at a given node index is currently withheld from
+    /// callers. Fully recomputed by every [`PruningStore::prune`] call, so
+    /// an index that becomes needed again (e.g. a newly marked leaf whose
+    /// path covers it) stops being withheld, as long as [`Self::compact`]
+    /// hasn't since actually discarded it.
+    dropped: Vec<bool>,
+    /// Whether `compact` has already called [`Store::free`] on a given node
+    /// index. Once set it never clears: the backing hash is gone for good,
+    /// so `dropped` must stay `true` for that index regardless of what a
+    /// later `prune` recomputes.
+    freed: Vec<bool>,
+    /// Lengths of `retention`/`dropped`/`freed` at each recorded checkpoint.
+    checkpoints: Vec<usize>,
+    _marker: PhantomData<T>,
+}
+
+impl<T, S> PruningStore<T, S>
+where
+    T: Clone,
+    S: Store<T>,
+{
+    pub fn new(inner: S) -> Self {
+        PruningStore {
+            inner,
+            retention: vec![],
+            dropped: vec![],
+            freed: vec![],
+            checkpoints: vec![],
+            _marker: PhantomData,
+        }
+    }
+
+    /// Tag the leaf at node index `idx` as [`Retention::Marked`].
+    pub fn mark(&mut self, idx: u64) {
+        if let Some(r) = self.retention.get_mut(idx as usize) {
+            *r = Retention::Marked;
+        }
+    }
+
+    /// Tag the leaf at node index `idx` as [`Retention::Ephemeral`] and
+    /// sweep any ancestors this was the last reason to retain.
+    pub fn unmark(&mut self, idx: u64) {
+        if let Some(r) = self.retention.get_mut(idx as usize) {
+            *r = Retention::Ephemeral;
+        }
+        self.prune();
+    }
+
+    /// Recompute, from scratch, which hashes are neither a current peak, a
+    /// `Marked` leaf, nor a sibling on a `Marked` leaf's family path to its
+    /// peak.
+    ///
+    /// This only updates bookkeeping: it never calls [`Store::free`], so an
+    /// index that is unneeded this round but is needed again in a later
+    /// round (e.g. because a different leaf got marked) simply stops being
+    /// withheld again — no one-way ratchet. Call [`Self::compact`] to
+    /// actually reclaim the storage for whatever is presently unneeded.
+    pub fn prune(&mut self) {
+        let size = self.dropped.len() as u64;
+        let peak_pos = peaks(size);
+        if peak_pos.is_empty() {
+            return;
+        }
+
+        let mut keep = vec![false; self.dropped.len()];
+        for &p in &peak_pos {
+            keep[(p - 1) as usize] = true;
+        }
+
+        for (idx, retention) in self.retention.iter().enumerate() {
+            if *retention != Retention::Marked {
+                continue;
+            }
+
+            let leaf_pos = idx as u64 + 1;
+            keep[idx] = true;
+
+            if let Some(&peak) = peak_pos.iter().find(|&&p| p >= leaf_pos) {
+                for &(_, sibling) in &family_path(leaf_pos, peak) {
+                    keep[(sibling - 1) as usize] = true;
+                }
+            }
+        }
+
+        for (idx, k) in keep.into_iter().enumerate() {
+            self.dropped[idx] = self.freed[idx] || !k;
+        }
+    }
+
+    /// Actually release every currently-withheld hash from the backing
+    /// store via [`Store::free`], so memory genuinely shrinks rather than
+    /// merely being hidden behind `dropped`.
+    ///
+    /// Unlike [`Self::prune`], this is a one-way operation: once an index
+    /// has been compacted, re-`mark`ing and re-`prune`ing cannot restore it,
+    /// since the backing hash is really gone. Mark a leaf before compacting
+    /// if you still need to produce a proof for it.
+    pub fn compact(&mut self) {
+        for idx in 0..self.dropped.len() {
+            if self.dropped[idx] && !self.freed[idx] {
+                self.freed[idx] = true;
+                self.inner.free(idx as u64);
+            }
+        }
+    }
+}
+
+impl<T, S> Store<T> for PruningStore<T, S>
+where
+    T: Clone,
+    S: Store<T>,
+{
+    fn append(&mut self, elem: &T, hashes: &[Hash]) -> Result<(), Error> {
+        self.inner.append(elem, hashes)?;
+
+        for _ in hashes {
+            self.retention.push(Retention::Ephemeral);
+            self.dropped.push(false);
+            self.freed.push(false);
+        }
+
+        Ok(())
+    }
+
+    fn hash_at(&self, idx: u64) -> Result<Hash, Error> {
+        if self.dropped.get(idx as usize).copied().unwrap_or(false) {
+            return Err(Error::Pruned(idx));
+        }
+
+        self.inner.hash_at(idx)
+    }
+
+    fn peak_hash_at(&self, idx: u64) -> Result<Hash, Error> {
+        if self.dropped.get(idx as usize).copied().unwrap_or(false) {
+            return Err(Error::Pruned(idx));
+        }
+
+        self.inner.peak_hash_at(idx)
+    }
+
+    fn checkpoint(&mut self, id: u64) {
+        self.checkpoints.push(self.dropped.len());
+        self.inner.checkpoint(id);
+    }
+
+    fn rewind(&mut self) -> Result<(), Error> {
+        self.inner.rewind()?;
+
+        let len = self.checkpoints.pop().ok_or(Error::NoCheckpoint)?;
+        self.retention.truncate(len);
+        self.dropped.truncate(len);
+        self.freed.truncate(len);
+
+        Ok(())
+    }
+
+    fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VecStore;
+
+    fn hash(v: u8) -> Hash {
+        let mut h = [0u8; 32];
+        h[0] = v;
+        h
+    }
+
+    // Builds the 7 node tree documented in `utils::family_path`, one append
+    // per node so each position gets its own retention/dropped slot.
+    fn seven_node_store() -> PruningStore<u8, VecStore<u8>> {
+        let mut store = PruningStore::new(VecStore::<u8>::new());
+        for v in 1..=7u8 {
+            store.append(&v, &[hash(v)]).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn unmarked_leaves_are_pruned() {
+        let mut store = seven_node_store();
+        store.prune();
+
+        // only the current peak (7) survives
+        assert!(store.hash_at(6).is_ok());
+        assert!(matches!(store.hash_at(0), Err(Error::Pruned(0))));
+        assert!(matches!(store.hash_at(3), Err(Error::Pruned(3))));
+    }
+
+    #[test]
+    fn marked_leaf_keeps_its_path() {
+        let mut store = seven_node_store();
+        // mark leaf at position 1 (idx 0)
+        store.mark(0);
+        store.prune();
+
+        // leaf 1 itself, its sibling 2, and the sibling 6 one level up survive;
+        // the intermediate parent 3 is recomputable from 1 and 2, so it isn't
+        // retained
+        assert!(store.hash_at(0).is_ok());
+        assert!(store.hash_at(1).is_ok());
+        assert!(store.hash_at(5).is_ok());
+        assert!(store.hash_at(6).is_ok());
+        assert!(matches!(store.hash_at(2), Err(Error::Pruned(2))));
+
+        // leaf 4/5 (idx 3/4) are unrelated and get pruned
+        assert!(matches!(store.hash_at(3), Err(Error::Pruned(3))));
+        assert!(matches!(store.hash_at(4), Err(Error::Pruned(4))));
+    }
+
+    #[test]
+    fn unmarking_frees_unreferenced_ancestors() {
+        let mut store = seven_node_store();
+        store.mark(0);
+        store.prune();
+        assert!(store.hash_at(1).is_ok());
+
+        store.unmark(0);
+        assert!(matches!(store.hash_at(1), Err(Error::Pruned(1))));
+    }
+
+    #[test]
+    fn prune_does_not_ratchet_across_calls() {
+        let mut store = seven_node_store();
+
+        store.mark(0);
+        store.prune();
+        assert!(store.hash_at(1).is_ok());
+        assert!(matches!(store.hash_at(3), Err(Error::Pruned(3))));
+
+        // a later prune with a different retention tag must not get stuck on
+        // what an earlier call withheld: node 4 (idx 3) becomes needed once
+        // it's marked, even though it was withheld a moment ago.
+        store.mark(3);
+        store.prune();
+        assert!(store.hash_at(3).is_ok());
+
+        // and unmarking it again withholds it again, cleanly, because
+        // `compact` was never called so nothing was actually discarded
+        store.unmark(3);
+        assert!(matches!(store.hash_at(3), Err(Error::Pruned(3))));
+        store.mark(3);
+        store.prune();
+        assert!(store.hash_at(3).is_ok());
+    }
+
+    #[test]
+    fn compact_actually_releases_backing_storage() {
+        let mut store = seven_node_store();
+        store.mark(0);
+        store.prune();
+        store.compact();
+
+        // idx 3 was never kept, so compact really discarded it from `inner`,
+        // not just behind the wrapper's own `dropped` bookkeeping
+        assert!(matches!(store.inner.hash_at(3), Err(Error::MissingHashAtIndex(3))));
+
+        // marking it again cannot resurrect what compact already discarded
+        store.mark(3);
+        store.prune();
+        assert!(matches!(store.hash_at(3), Err(Error::Pruned(3))));
+    }
+}
@@ -30,13 +30,40 @@ where
     fn hash_at(&self, idx: u64) -> Result<Hash, Error>;
 
     fn peak_hash_at(&self, idx: u64) -> Result<Hash, Error>;
+
+    /// Record a checkpoint tagged `id` at the store's current size.
+    ///
+    /// A later [`Store::rewind`] discards everything appended since the most
+    /// recent checkpoint.
+    fn checkpoint(&mut self, id: u64);
+
+    /// Discard everything appended since the most recent checkpoint and pop
+    /// it, restoring the store to the size it had when checkpointed.
+    ///
+    /// Returns [`Error::NoCheckpoint`] if no checkpoint has been recorded.
+    fn rewind(&mut self) -> Result<(), Error>;
+
+    /// Number of checkpoints currently recorded.
+    fn checkpoint_count(&self) -> usize;
+
+    /// Release the hash at node index `idx`, reclaiming its storage.
+    ///
+    /// After this call, [`Store::hash_at`]/[`Store::peak_hash_at`] for `idx`
+    /// return [`Error::MissingHashAtIndex`]. Stores that cannot reclaim
+    /// individual slots (e.g. a fixed-record file format) may leave this a
+    /// no-op.
+    fn free(&mut self, _idx: u64) {}
 }
 
 pub struct VecStore<T> {
     /// Optional store elements, `None` if only hashes are stored.
     pub data: Option<Vec<T>>,
-    /// MMR hashes for both, laves and parents
-    pub hashes: Vec<Hash>,
+    /// MMR hashes for both leaves and parents, `None` at an index whose hash
+    /// has been released via [`Store::free`].
+    pub hashes: Vec<Option<Hash>>,
+    /// Stack of `(checkpoint_id, hashes_len, data_len)` records, most recent
+    /// last, used by [`Store::rewind`] to truncate back to a checkpoint.
+    checkpoints: Vec<(u64, usize, usize)>,
 }
 
 impl<T> Store<T> for VecStore<T>
@@ -48,7 +75,7 @@ where
             data.push(elem.clone());
         }
 
-        self.hashes.extend_from_slice(hashes);
+        self.hashes.extend(hashes.iter().copied().map(Some));
 
         Ok(())
     }
@@ -57,14 +84,38 @@ where
         self.hashes
             .get(idx as usize)
             .cloned()
-            .ok_or_else(|| Error::MissingHashAtIndex(idx))
+            .flatten()
+            .ok_or(Error::MissingHashAtIndex(idx))
     }
 
     fn peak_hash_at(&self, idx: u64) -> Result<Hash, Error> {
-        self.hashes
-            .get(idx as usize)
-            .cloned()
-            .ok_or_else(|| Error::MissingHashAtIndex(idx))
+        self.hash_at(idx)
+    }
+
+    fn checkpoint(&mut self, id: u64) {
+        let data_len = self.data.as_ref().map_or(0, Vec::len);
+        self.checkpoints.push((id, self.hashes.len(), data_len));
+    }
+
+    fn rewind(&mut self) -> Result<(), Error> {
+        let (_, hashes_len, data_len) = self.checkpoints.pop().ok_or(Error::NoCheckpoint)?;
+
+        self.hashes.truncate(hashes_len);
+        if let Some(data) = &mut self.data {
+            data.truncate(data_len);
+        }
+
+        Ok(())
+    }
+
+    fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    fn free(&mut self, idx: u64) {
+        if let Some(slot) = self.hashes.get_mut(idx as usize) {
+            *slot = None;
+        }
     }
 }
 
@@ -73,6 +124,7 @@ impl<T> VecStore<T> {
         VecStore {
             data: Some(vec![]),
             hashes: vec![],
+            checkpoints: vec![],
         }
     }
 }
@@ -0,0 +1,79 @@
+// Copyright (C) 2021 Andreas Doerr
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{Store, VecStore};
+
+fn hash(v: u8) -> crate::Hash {
+    let mut h = [0u8; 32];
+    h[0] = v;
+    h
+}
+
+#[test]
+fn append_and_read_back() {
+    let mut store = VecStore::<u8>::new();
+
+    store.append(&1, &[hash(1)]).unwrap();
+    store.append(&2, &[hash(2)]).unwrap();
+
+    assert_eq!(store.hash_at(0).unwrap(), hash(1));
+    assert_eq!(store.hash_at(1).unwrap(), hash(2));
+    assert!(store.hash_at(2).is_err());
+}
+
+#[test]
+fn checkpoint_and_rewind() {
+    let mut store = VecStore::<u8>::new();
+
+    store.append(&1, &[hash(1)]).unwrap();
+    store.checkpoint(0);
+
+    store.append(&2, &[hash(2)]).unwrap();
+    store.append(&3, &[hash(3)]).unwrap();
+    assert_eq!(store.checkpoint_count(), 1);
+
+    store.rewind().unwrap();
+
+    assert_eq!(store.hashes.len(), 1);
+    assert_eq!(store.data.as_ref().unwrap().len(), 1);
+    assert_eq!(store.checkpoint_count(), 0);
+}
+
+#[test]
+fn rewind_without_checkpoint_fails() {
+    let mut store = VecStore::<u8>::new();
+    assert!(store.rewind().is_err());
+}
+
+#[test]
+fn nested_checkpoints_rewind_in_order() {
+    let mut store = VecStore::<u8>::new();
+
+    store.append(&1, &[hash(1)]).unwrap();
+    store.checkpoint(0);
+
+    store.append(&2, &[hash(2)]).unwrap();
+    store.checkpoint(1);
+
+    store.append(&3, &[hash(3)]).unwrap();
+
+    store.rewind().unwrap();
+    assert_eq!(store.hashes.len(), 2);
+
+    store.rewind().unwrap();
+    assert_eq!(store.hashes.len(), 1);
+
+    assert!(store.rewind().is_err());
+}
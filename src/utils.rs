@@ -15,7 +15,7 @@
 
 //! Utiility functions mainly for MMR navigation
 
-use crate::{vec, Vec};
+use crate::{vec, Hash, Merge, Vec};
 
 /// 64-bit all being binary ones: 0b1111111...1
 const ALL_ONES: u64 = u64::MAX;
@@ -208,6 +208,37 @@ pub(crate) fn family_path(pos: u64, end_pos: u64) -> Vec<(u64, u64)> {
     path
 }
 
+/// Fold `node_hash` (the hash of the node at `pos`) up a `family_path`,
+/// combining it with the corresponding `siblings` at each step, and return
+/// the resulting ancestor hash.
+///
+/// `siblings` must be aligned with `path`, i.e. `siblings[i]` is the hash of
+/// `path[i].1`. The fold order at each step is determined by [`is_left`] so
+/// that the reconstructed hash matches the one originally computed on
+/// append.
+pub(crate) fn fold_path<M: Merge>(pos: u64, node_hash: Hash, path: &[(u64, u64)], siblings: &[Hash]) -> Hash {
+    let mut cur_pos = pos;
+    let mut acc = node_hash;
+
+    for (&(parent, _), sibling) in path.iter().zip(siblings.iter()) {
+        acc = if is_left(cur_pos) {
+            M::merge(&acc, sibling)
+        } else {
+            M::merge(sibling, &acc)
+        };
+        cur_pos = parent;
+    }
+
+    acc
+}
+
+/// Bag a left-to-right list of peak hashes into a single root hash.
+pub(crate) fn bag<M: Merge>(hashes: &[Hash]) -> Option<Hash> {
+    let mut iter = hashes.iter();
+    let first = *iter.next()?;
+    Some(iter.fold(first, |acc, h| M::merge(&acc, h)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::{family, family_path, is_leaf, is_left, node_height, peak_height_map, peaks};